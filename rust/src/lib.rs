@@ -1,4 +1,6 @@
+use aho_corasick::AhoCorasick;
 use pyo3::prelude::*;
+use rayon::prelude::*;
 use std::collections::HashMap;
 
 /// High-performance suffix array-based substring index implemented in Rust.
@@ -78,6 +80,66 @@ impl RustSubstringIndex {
     /// Returns:
     ///     List of indices where typo appears as substring (excluding self)
     pub fn find_substring_conflicts(&self, typo: &str) -> PyResult<Vec<usize>> {
+        Ok(self.conflicts_for(typo))
+    }
+
+    /// Find every typo's containment graph in a single GIL-free pass.
+    ///
+    /// Runs every typo through the suffix-array lookup in parallel with the GIL
+    /// released, returning a map from each typo's index to the indices of the
+    /// typos that contain it as a substring (self excluded). Typos with no
+    /// conflicts are omitted from the map.
+    ///
+    /// Returns:
+    ///     Dict mapping typo index -> list of containing typo indices
+    pub fn find_all_conflicts(&self, py: Python<'_>) -> HashMap<usize, Vec<usize>> {
+        py.allow_threads(|| {
+            self.typos
+                .par_iter()
+                .enumerate()
+                .filter_map(|(i, typo)| {
+                    let conflicts = self.conflicts_for(typo);
+                    if conflicts.is_empty() {
+                        None
+                    } else {
+                        Some((i, conflicts))
+                    }
+                })
+                .collect()
+        })
+    }
+
+    /// Like `find_all_conflicts` but restricted to the given query indices.
+    ///
+    /// Out-of-range indices are ignored. Runs with the GIL released.
+    ///
+    /// Returns:
+    ///     Dict mapping query index -> list of containing typo indices
+    pub fn find_conflicts_for_indices(
+        &self,
+        py: Python<'_>,
+        indices: Vec<usize>,
+    ) -> HashMap<usize, Vec<usize>> {
+        py.allow_threads(|| {
+            indices
+                .par_iter()
+                .filter(|&&i| i < self.typos.len())
+                .map(|&i| (i, self.conflicts_for(&self.typos[i])))
+                .filter(|(_, conflicts)| !conflicts.is_empty())
+                .collect()
+        })
+    }
+
+    /// Get the list of typos (for compatibility/testing).
+    pub fn get_typos(&self) -> Vec<String> {
+        self.typos.clone()
+    }
+}
+
+impl RustSubstringIndex {
+    /// Look up the indices of the typos that contain `typo` as a substring,
+    /// excluding `typo` itself. Shared by the single- and batch-query entry points.
+    fn conflicts_for(&self, typo: &str) -> Vec<usize> {
         // Find all occurrences using suffix array
         // The suffix crate's positions() method returns &[u32] (slice of positions)
         let matches = self.suffix_array.positions(typo);
@@ -112,59 +174,33 @@ impl RustSubstringIndex {
 
         // Filter out self-matches
         let self_idx = self.typo_to_idx.get(typo);
-        let result: Vec<usize> = matched_typo_indices
+        matched_typo_indices
             .into_iter()
             .filter(|idx| Some(idx) != self_idx)
-            .collect();
-
-        Ok(result)
-    }
-
-    /// Get the list of typos (for compatibility/testing).
-    pub fn get_typos(&self) -> Vec<String> {
-        self.typos.clone()
-    }
-}
-
-/// Check if a pattern would corrupt a source word for RTL matching.
-///
-/// For RTL: checks if pattern appears at word boundaries at the start
-/// (position 0 or after a non-alpha character).
-fn would_corrupt_rtl(pattern: &str, source_word: &str) -> bool {
-    let mut idx = 0;
-    while let Some(pos) = source_word[idx..].find(pattern) {
-        let absolute_pos = idx + pos;
-        // Check if there's a word boundary before the pattern
-        if absolute_pos == 0 || !source_word.chars().nth(absolute_pos - 1).map_or(false, |c| c.is_alphabetic()) {
-            return true;
-        }
-        idx = absolute_pos + 1;
-        if idx >= source_word.len() {
-            break;
-        }
+            .collect()
     }
-    false
 }
 
-/// Check if a pattern would corrupt a source word for LTR matching.
+/// Test whether a single Aho-Corasick occurrence corrupts its source word.
 ///
-/// For LTR: checks if pattern appears at word boundaries at the end
-/// (at end of word or before a non-alpha character).
-fn would_corrupt_ltr(pattern: &str, source_word: &str) -> bool {
-    let mut idx = 0;
-    while let Some(pos) = source_word[idx..].find(pattern) {
-        let absolute_pos = idx + pos;
-        let char_after_idx = absolute_pos + pattern.len();
-        // Check if there's a word boundary after the pattern
-        if char_after_idx >= source_word.len() || !source_word.chars().nth(char_after_idx).map_or(false, |c| c.is_alphabetic()) {
-            return true;
-        }
-        idx = absolute_pos + 1;
-        if idx >= source_word.len() {
-            break;
-        }
+/// For RTL the pattern corrupts when it sits at the start of the word
+/// (`match_start == 0` or the preceding char is non-alphabetic). For LTR it
+/// corrupts when the match ends at the word end or the following char is
+/// non-alphabetic.
+fn occurrence_corrupts(source_word: &str, start: usize, end: usize, is_rtl: bool) -> bool {
+    if is_rtl {
+        start == 0
+            || !source_word[..start]
+                .chars()
+                .next_back()
+                .map_or(false, |c| c.is_alphabetic())
+    } else {
+        end >= source_word.len()
+            || !source_word[end..]
+                .chars()
+                .next()
+                .map_or(false, |c| c.is_alphabetic())
     }
-    false
 }
 
 /// Batch check if patterns would corrupt source words.
@@ -176,29 +212,255 @@ fn would_corrupt_ltr(pattern: &str, source_word: &str) -> bool {
 ///     patterns: List of typo patterns to check
 ///     source_words: List of source words to check against
 ///     match_direction: "RTL" for RIGHT_TO_LEFT, "LTR" for LEFT_TO_RIGHT
+///     num_threads: Optional cap on the number of worker threads (0 or None = all cores)
 ///
 /// Returns:
 ///     List of booleans, True if pattern would corrupt any source word
 #[pyfunction]
+#[pyo3(signature = (patterns, source_words, match_direction, num_threads = None))]
 fn batch_check_patterns(
+    py: Python<'_>,
     patterns: Vec<String>,
     source_words: Vec<String>,
     match_direction: String,
+    num_threads: Option<usize>,
 ) -> PyResult<Vec<bool>> {
     let is_rtl = match_direction.as_str() == "RTL" || match_direction.as_str() == "RIGHT_TO_LEFT";
 
-    let results: Vec<bool> = patterns
-        .iter()
-        .map(|pattern| {
-            if is_rtl {
-                source_words.iter().any(|word| would_corrupt_rtl(pattern, word))
-            } else {
-                source_words.iter().any(|word| would_corrupt_ltr(pattern, word))
+    // Release the GIL for the automaton build and the Rayon-parallel scan so
+    // neither contends for the Python interpreter lock.
+    let results = py.allow_threads(|| {
+        let scan = || {
+            // Build one automaton over the whole pattern list (done once per call).
+            // An empty pattern list has no possible corruptions.
+            if patterns.is_empty() {
+                return Ok(Vec::new());
+            }
+            let automaton = AhoCorasick::new(&patterns).map_err(|e| {
+                pyo3::exceptions::PyRuntimeError::new_err(format!(
+                    "failed to build Aho-Corasick automaton: {e}"
+                ))
+            })?;
+
+            // Run every source word through the automaton once, collecting the
+            // ids of the patterns each occurrence corrupts. Cost is O(total
+            // source length + total matches + patterns) rather than quadratic.
+            let corrupting: Vec<usize> = source_words
+                .par_iter()
+                .flat_map_iter(|word| {
+                    automaton
+                        .find_overlapping_iter(word)
+                        .filter(move |m| {
+                            occurrence_corrupts(word, m.start(), m.end(), is_rtl)
+                        })
+                        .map(|m| m.pattern().as_usize())
+                })
+                .collect();
+
+            let mut results = vec![false; patterns.len()];
+            for id in corrupting {
+                results[id] = true;
             }
+            Ok(results)
+        };
+
+        // When a thread cap is requested, run the scan inside a dedicated pool so
+        // the global Rayon pool is left untouched; otherwise use all cores.
+        match num_threads {
+            Some(n) if n > 0 => rayon::ThreadPoolBuilder::new()
+                .num_threads(n)
+                .build()
+                .map_err(|e| {
+                    pyo3::exceptions::PyRuntimeError::new_err(format!(
+                        "failed to build thread pool: {e}"
+                    ))
+                })
+                .and_then(|pool| pool.install(scan)),
+            _ => scan(),
+        }
+    })?;
+
+    Ok(results)
+}
+
+/// Decide how many autocorrect variants each source word should receive.
+///
+/// Models a length-scaled typo budget similar to typo-tolerance search systems:
+/// short words and explicitly protected ("exact") words get no corrections,
+/// longer words get one, and the longest get two. This keeps noisy corrections
+/// off short or protected words (brand names, acronyms) without hand-curated lists.
+///
+/// Args:
+///     words: Source words to classify
+///     exact_words: Words that should never receive corrections
+///     one_typo_len: Minimum length for a single variant (default 5)
+///     two_typo_len: Minimum length for two variants (default 9)
+///
+/// Returns:
+///     List of (word, variant_count) pairs, one per input word
+#[pyfunction]
+#[pyo3(signature = (words, exact_words, one_typo_len = 5, two_typo_len = 9))]
+fn filter_by_typo_policy(
+    words: Vec<String>,
+    exact_words: Vec<String>,
+    one_typo_len: usize,
+    two_typo_len: usize,
+) -> PyResult<Vec<(String, u8)>> {
+    // O(1) membership for the protected set.
+    let exact: std::collections::HashSet<String> = exact_words.into_iter().collect();
+
+    let result = words
+        .into_iter()
+        .map(|word| {
+            let len = word.chars().count();
+            let variants = if exact.contains(&word) || len < one_typo_len {
+                0
+            } else if len >= two_typo_len {
+                2
+            } else {
+                1
+            };
+            (word, variants)
         })
         .collect();
 
-    Ok(results)
+    Ok(result)
+}
+
+/// Maximum word length we will generate typos for. Beyond this the candidate
+/// set grows without producing useful corrections, so we bail out early.
+const MAX_TYPO_WORD_LEN: usize = 24;
+
+/// QWERTY neighbors for a lowercase letter, used to restrict edits to realistic
+/// fat-finger errors. Returns an empty slice for characters not on the map.
+fn keyboard_neighbors(c: char) -> &'static [char] {
+    match c {
+        'q' => &['w', 'a', 's'],
+        'w' => &['q', 'e', 'a', 's', 'd'],
+        'e' => &['w', 'r', 's', 'd', 'f'],
+        'r' => &['e', 't', 'd', 'f', 'g'],
+        't' => &['r', 'y', 'f', 'g', 'h'],
+        'y' => &['t', 'u', 'g', 'h', 'j'],
+        'u' => &['y', 'i', 'h', 'j', 'k'],
+        'i' => &['u', 'o', 'j', 'k', 'l'],
+        'o' => &['i', 'p', 'k', 'l'],
+        'p' => &['o', 'l'],
+        'a' => &['q', 'w', 's', 'z'],
+        's' => &['q', 'w', 'e', 'a', 'd', 'z', 'x'],
+        'd' => &['w', 'e', 'r', 's', 'f', 'x', 'c'],
+        'f' => &['e', 'r', 't', 'd', 'g', 'c', 'v'],
+        'g' => &['r', 't', 'y', 'f', 'h', 'v', 'b'],
+        'h' => &['t', 'y', 'u', 'g', 'j', 'b', 'n'],
+        'j' => &['y', 'u', 'i', 'h', 'k', 'n', 'm'],
+        'k' => &['u', 'i', 'o', 'j', 'l', 'm'],
+        'l' => &['i', 'o', 'p', 'k'],
+        'z' => &['a', 's', 'x'],
+        'x' => &['s', 'd', 'z', 'c'],
+        'c' => &['d', 'f', 'x', 'v'],
+        'v' => &['f', 'g', 'c', 'b'],
+        'b' => &['g', 'h', 'v', 'n'],
+        'n' => &['h', 'j', 'b', 'm'],
+        'm' => &['j', 'k', 'n'],
+        _ => &[],
+    }
+}
+
+/// Candidate replacement/insertion characters for a position.
+///
+/// With keyboard adjacency on, only the QWERTY neighbors of `c` are used (for
+/// insertions `c` is the character at the gap); otherwise the full a-z alphabet.
+fn edit_chars(c: char, keyboard_adjacent: bool) -> Vec<char> {
+    if keyboard_adjacent {
+        keyboard_neighbors(c).to_vec()
+    } else {
+        ('a'..='z').collect()
+    }
+}
+
+/// Generate all single-edit (distance 1) variants of `chars`.
+fn single_edits(chars: &[char], keyboard_adjacent: bool) -> std::collections::HashSet<String> {
+    let mut out = std::collections::HashSet::new();
+    let n = chars.len();
+
+    // Deletion: drop each character.
+    for i in 0..n {
+        let mut v: Vec<char> = chars.to_vec();
+        v.remove(i);
+        out.insert(v.into_iter().collect());
+    }
+
+    // Transposition (Damerau): swap each adjacent pair.
+    for i in 0..n.saturating_sub(1) {
+        let mut v: Vec<char> = chars.to_vec();
+        v.swap(i, i + 1);
+        out.insert(v.into_iter().collect());
+    }
+
+    // Substitution: replace each character with a candidate.
+    for i in 0..n {
+        for r in edit_chars(chars[i], keyboard_adjacent) {
+            if r == chars[i] {
+                continue;
+            }
+            let mut v: Vec<char> = chars.to_vec();
+            v[i] = r;
+            out.insert(v.into_iter().collect());
+        }
+    }
+
+    // Insertion: insert a candidate at each gap (including the ends). The gap's
+    // anchor for adjacency is the character at that position (or the last one).
+    for i in 0..=n {
+        let anchor = chars.get(i).or_else(|| chars.last()).copied().unwrap_or('a');
+        for ins in edit_chars(anchor, keyboard_adjacent) {
+            let mut v: Vec<char> = chars.to_vec();
+            v.insert(i, ins);
+            out.insert(v.into_iter().collect());
+        }
+    }
+
+    out
+}
+
+/// Generate misspelling candidates for a word via single-character edits.
+///
+/// Produces deletion, transposition, substitution and insertion variants up to
+/// `max_distance` edits away (distance 2 applies the generator to each distance-1
+/// result). When `keyboard_adjacent` is true, substitutions and insertions are
+/// restricted to QWERTY-adjacent characters so the output matches realistic
+/// fat-finger errors. The original word is never included.
+///
+/// Args:
+///     word: The word to perturb
+///     max_distance: Maximum edit distance (1 or 2)
+///     keyboard_adjacent: Restrict edits to QWERTY-adjacent characters
+///
+/// Returns:
+///     Deduplicated list of candidate misspellings
+#[pyfunction]
+fn generate_typos(word: &str, max_distance: u8, keyboard_adjacent: bool) -> Vec<String> {
+    let chars: Vec<char> = word.chars().collect();
+    // Bail out on empty or pathologically long words to avoid candidate blowup.
+    if chars.is_empty() || chars.len() > MAX_TYPO_WORD_LEN {
+        return Vec::new();
+    }
+
+    let mut candidates = single_edits(&chars, keyboard_adjacent);
+
+    if max_distance >= 2 {
+        let distance_one: Vec<Vec<char>> =
+            candidates.iter().map(|s| s.chars().collect()).collect();
+        for variant in distance_one {
+            if variant.len() > MAX_TYPO_WORD_LEN {
+                continue;
+            }
+            candidates.extend(single_edits(&variant, keyboard_adjacent));
+        }
+    }
+
+    // Never emit the original word itself.
+    candidates.remove(word);
+    candidates.into_iter().collect()
 }
 
 /// Python module definition
@@ -206,5 +468,7 @@ fn batch_check_patterns(
 fn rust_ext(_py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<RustSubstringIndex>()?;
     m.add_function(wrap_pyfunction!(batch_check_patterns, m)?)?;
+    m.add_function(wrap_pyfunction!(filter_by_typo_policy, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_typos, m)?)?;
     Ok(())
 }